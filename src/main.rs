@@ -3,6 +3,18 @@ use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::io::{self, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+use regex::{Regex, RegexBuilder};
+
+/// Output format for search results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
 
 /// Configuration for the search operation
 #[derive(Debug)]
@@ -13,6 +25,15 @@ struct Config {
     case_sensitive: bool,
     show_line_content: bool,
     output_file: Option<PathBuf>,
+    regex: Option<Regex>,
+    threads: usize,
+    no_ignore: bool,
+    format: Format,
+    before_context: usize,
+    after_context: usize,
+    use_stdin: bool,
+    replace: Option<String>,
+    dry_run: bool,
 }
 
 impl Config {
@@ -29,6 +50,15 @@ impl Config {
         let mut case_sensitive = false;
         let mut show_line_content = false;
         let mut output_file: Option<PathBuf> = None;
+        let mut use_regex = false;
+        let mut threads: Option<usize> = None;
+        let mut no_ignore = false;
+        let mut format = Format::Text;
+        let mut before_context = 0usize;
+        let mut after_context = 0usize;
+        let mut use_stdin = false;
+        let mut replace: Option<String> = None;
+        let mut dry_run = false;
 
         let mut i = 1;
         while i < args.len() {
@@ -69,6 +99,73 @@ impl Config {
                     show_line_content = true;
                     i += 1;
                 }
+                "-r" | "--regex" => {
+                    use_regex = true;
+                    i += 1;
+                }
+                "-j" | "--threads" => {
+                    if let Some(val) = Self::get_next_arg(&args, &mut i, "thread count")? {
+                        let parsed = val
+                            .parse::<usize>()
+                            .map_err(|_| format!("Invalid thread count: {}", val))?;
+                        if parsed == 0 {
+                            return Err("Thread count must be at least 1".to_string());
+                        }
+                        threads = Some(parsed);
+                    }
+                }
+                "--no-ignore" => {
+                    no_ignore = true;
+                    i += 1;
+                }
+                "--stdin" => {
+                    use_stdin = true;
+                    i += 1;
+                }
+                "--replace" => {
+                    replace = Self::get_next_arg(&args, &mut i, "replacement text")?;
+                }
+                "--dry-run" => {
+                    dry_run = true;
+                    i += 1;
+                }
+                "-B" | "--before-context" => {
+                    if let Some(val) = Self::get_next_arg(&args, &mut i, "before-context count")? {
+                        before_context = val
+                            .parse::<usize>()
+                            .map_err(|_| format!("Invalid before-context count: {}", val))?;
+                    }
+                }
+                "-A" | "--after-context" => {
+                    if let Some(val) = Self::get_next_arg(&args, &mut i, "after-context count")? {
+                        after_context = val
+                            .parse::<usize>()
+                            .map_err(|_| format!("Invalid after-context count: {}", val))?;
+                    }
+                }
+                "-C" | "--context" => {
+                    if let Some(val) = Self::get_next_arg(&args, &mut i, "context count")? {
+                        let parsed = val
+                            .parse::<usize>()
+                            .map_err(|_| format!("Invalid context count: {}", val))?;
+                        before_context = parsed;
+                        after_context = parsed;
+                    }
+                }
+                "--format" => {
+                    if let Some(val) = Self::get_next_arg(&args, &mut i, "format")? {
+                        format = match val.as_str() {
+                            "text" => Format::Text,
+                            "json" => Format::Json,
+                            other => {
+                                return Err(format!(
+                                    "Unknown format: {} (expected 'text' or 'json')",
+                                    other
+                                ))
+                            }
+                        };
+                    }
+                }
                 "-h" | "--help" => {
                     return Err(Self::usage());
                 }
@@ -82,7 +179,24 @@ impl Config {
             }
         }
 
-        let path = Self::resolve_path(path)?;
+        if dry_run && replace.is_none() {
+            return Err(format!("--dry-run requires --replace\n\n{}", Self::usage()));
+        }
+        if replace.is_some() && (use_stdin || path.as_deref() == Some("-")) {
+            return Err(format!(
+                "--replace cannot be combined with --stdin\n\n{}",
+                Self::usage()
+            ));
+        }
+
+        // `-p -` is the grep/ripgrep convention for "read from stdin".
+        let use_stdin = use_stdin || path.as_deref() == Some("-");
+
+        let path = if use_stdin {
+            PathBuf::from("<stdin>")
+        } else {
+            Self::resolve_path(path)?
+        };
         let search_text =
             search_text.ok_or_else(|| format!("Search text is required\n\n{}", Self::usage()))?;
 
@@ -103,6 +217,29 @@ impl Config {
             ]
         });
 
+        // Context lines are only meaningful alongside the matched line's own
+        // content, so requesting context implies -l/--show-lines.
+        if before_context > 0 || after_context > 0 {
+            show_line_content = true;
+        }
+
+        let threads = threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        });
+
+        let regex = if use_regex {
+            Some(
+                RegexBuilder::new(&search_text)
+                    .case_insensitive(!case_sensitive)
+                    .build()
+                    .map_err(|e| format!("Invalid regex pattern '{}': {}", search_text, e))?,
+            )
+        } else {
+            None
+        };
+
         Ok(Config {
             path,
             search_text,
@@ -110,6 +247,15 @@ impl Config {
             case_sensitive,
             show_line_content,
             output_file,
+            regex,
+            threads,
+            no_ignore,
+            format,
+            before_context,
+            after_context,
+            use_stdin,
+            replace,
+            dry_run,
         })
     }
 
@@ -159,19 +305,37 @@ USAGE:
     kemet -s <search_text> [OPTIONS]
 
 OPTIONS:
-    -p, --path <PATH>           Directory to search (default: current directory)
+    -p, --path <PATH>           Directory to search (default: current directory); use "-" to read from stdin
     -s, --search <TEXT>         Text to search for (required)
     -e, --extensions <EXT>      Comma-separated file extensions (default: txt,json,cs,sql,config,rs,py,js,ts,html,css,xml)
     -o, --output <FILE>         Output file path (if not provided, results shown on console)
     -c, --case-sensitive        Enable case-sensitive search
     -l, --show-lines           Show matching line content
+    -r, --regex                 Treat search text as a regular expression
+    -j, --threads <N>            Number of worker threads (default: available parallelism)
+    --no-ignore                  Do not respect .gitignore/.ignore files or the global ignore file
+    --stdin                       Read lines from stdin instead of walking a directory
+    --format <text|json>         Output format (default: text)
+    -B, --before-context <N>     Show N lines of context before each match
+    -A, --after-context <N>      Show N lines of context after each match
+    -C, --context <N>            Show N lines of context before and after each match
+    --replace <TEXT>              Replace each match with TEXT (use $1, $2, ... for capture groups with -r)
+    --dry-run                     With --replace, preview changes without writing to disk
     -h, --help                 Show this help message
 
 EXAMPLES:
     kemet -s "function"
     kemet -p /home/user/code -s "TODO" -e "rs,py,js"
     kemet -s "Error" -c -l
-    kemet -s "function" -o results.txt"#.to_string()
+    kemet -s "function" -o results.txt
+    kemet -s "TODO\(.*\)" -r
+    kemet -s "TODO" -j 8
+    kemet -s "TODO" --no-ignore
+    kemet -s "TODO" --format json
+    kemet -s "TODO" -C 2
+    cat big.log | kemet -s ERROR -l --stdin
+    kemet -s "foo" --replace "bar" --dry-run
+    kemet -s "TODO\((\w+)\)" -r --replace "FIXME($1)""#.to_string()
     }
 }
 
@@ -215,18 +379,40 @@ struct Match {
     file_path: PathBuf,
     line_number: usize,
     line_content: Option<String>,
+    matched_ranges: Vec<(usize, usize)>,
+    /// Lines preceding the match requested via `-B`/`-C`, oldest first.
+    before_context: Vec<(usize, String)>,
+    /// Lines following the match requested via `-A`/`-C`, in file order.
+    after_context: Vec<(usize, String)>,
 }
 
 impl Match {
-    fn new(file_path: PathBuf, line_number: usize, line_content: Option<String>) -> Self {
+    fn new(
+        file_path: PathBuf,
+        line_number: usize,
+        line_content: Option<String>,
+        matched_ranges: Vec<(usize, usize)>,
+        before_context: Vec<(usize, String)>,
+        after_context: Vec<(usize, String)>,
+    ) -> Self {
         Self {
             file_path,
             line_number,
             line_content,
+            matched_ranges,
+            before_context,
+            after_context,
         }
     }
 
     fn format_output(&self, config: &Config) -> String {
+        match config.format {
+            Format::Text => self.format_text(config),
+            Format::Json => self.format_json(),
+        }
+    }
+
+    fn format_text(&self, config: &Config) -> String {
         if config.show_line_content {
             if let Some(ref content) = self.line_content {
                 format!(
@@ -242,12 +428,273 @@ impl Match {
             format!("{} (Line {})", self.file_path.display(), self.line_number)
         }
     }
+
+    fn format_json(&self) -> String {
+        let line = self.line_content.as_deref().unwrap_or("");
+        let submatches: Vec<String> = self
+            .matched_ranges
+            .iter()
+            .map(|(start, end)| format!(r#"{{"start":{},"end":{}}}"#, start, end))
+            .collect();
+
+        format!(
+            r#"{{"type":"match","path":"{}","line_number":{},"line":"{}","submatches":[{}]}}"#,
+            json_escape(&self.file_path.display().to_string()),
+            self.line_number,
+            json_escape(line),
+            submatches.join(",")
+        )
+    }
+}
+
+/// Returns the byte ranges of every non-overlapping case-insensitive
+/// occurrence of `needle_lower` (already lowercased) in `haystack`, found by
+/// scanning `haystack` itself rather than by comparing against a separately
+/// lowercased copy of it. Unicode case folding can change a character's byte
+/// length (e.g. the Turkish dotted capital `İ` lowercases to `i̇`, two
+/// characters), which would make offsets computed on a folded copy invalid
+/// for the original string; scanning `haystack` directly keeps every
+/// returned offset a valid index into it.
+fn find_case_insensitive(haystack: &str, needle_lower: &str) -> Vec<(usize, usize)> {
+    if needle_lower.is_empty() {
+        return Vec::new();
+    }
+
+    let mut ranges = Vec::new();
+    let mut pos = 0;
+    while pos < haystack.len() {
+        if let Some(len) = case_insensitive_match_len(&haystack[pos..], needle_lower) {
+            ranges.push((pos, pos + len));
+            pos += len;
+        } else {
+            pos += haystack[pos..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    ranges
+}
+
+/// Returns whether `haystack` contains a case-insensitive occurrence of
+/// `needle_lower` (already lowercased), without allocating a ranges vector.
+fn contains_case_insensitive(haystack: &str, needle_lower: &str) -> bool {
+    if needle_lower.is_empty() {
+        return true;
+    }
+
+    let mut pos = 0;
+    while pos < haystack.len() {
+        if case_insensitive_match_len(&haystack[pos..], needle_lower).is_some() {
+            return true;
+        }
+        pos += haystack[pos..].chars().next().map_or(1, char::len_utf8);
+    }
+    false
+}
+
+/// If `haystack` starts, case-insensitively, with `needle_lower`, returns
+/// how many bytes of `haystack` that occurrence consumed.
+fn case_insensitive_match_len(haystack: &str, needle_lower: &str) -> Option<usize> {
+    let mut folded = String::with_capacity(needle_lower.len());
+    let mut consumed = 0;
+    for c in haystack.chars() {
+        if folded.len() >= needle_lower.len() {
+            break;
+        }
+        consumed += c.len_utf8();
+        for lower_c in c.to_lowercase() {
+            folded.push(lower_c);
+        }
+    }
+    if folded == needle_lower {
+        Some(consumed)
+    } else {
+        None
+    }
+}
+
+/// Escapes a string for embedding in a JSON string literal.
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A single compiled rule parsed from a `.gitignore`/`.ignore` line.
+struct IgnoreRule {
+    regex: Regex,
+    negate: bool,
+    dir_only: bool,
+}
+
+impl IgnoreRule {
+    /// Parses one line of a `.gitignore`/`.ignore` file, returning `None`
+    /// for blank lines and comments.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let mut pattern = line;
+        let negate = pattern.starts_with('!');
+        if negate {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        if dir_only {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+
+        // A pattern with a `/` anywhere but the trailing position is
+        // anchored to the directory holding the ignore file; a pattern
+        // with no `/` at all matches the basename at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        let body = Self::glob_to_regex_body(pattern);
+        let source = if anchored {
+            format!("^{}$", body)
+        } else {
+            format!("^(?:.*/)?{}$", body)
+        };
+
+        let regex = RegexBuilder::new(&source).build().ok()?;
+
+        Some(Self {
+            regex,
+            negate,
+            dir_only,
+        })
+    }
+
+    /// Translates a gitignore glob (`*`, `**`, `?`) into the body of an
+    /// equivalent regular expression.
+    fn glob_to_regex_body(pattern: &str) -> String {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut out = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '*' => {
+                    if chars.get(i + 1) == Some(&'*') {
+                        i += 2;
+                        if chars.get(i) == Some(&'/') {
+                            // `**/` matches zero or more whole path
+                            // components, not an arbitrary substring, so
+                            // `**/foo.rs` must not match `xfoo.rs`.
+                            out.push_str("(?:.*/)?");
+                            i += 1;
+                        } else {
+                            out.push_str(".*");
+                        }
+                    } else {
+                        out.push_str("[^/]*");
+                        i += 1;
+                    }
+                }
+                '?' => {
+                    out.push_str("[^/]");
+                    i += 1;
+                }
+                c => {
+                    if "\\.+()|[]{}^$".contains(c) {
+                        out.push('\\');
+                    }
+                    out.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Locates the user's global ignore file, following the `fd`/ripgrep
+/// convention of `$XDG_CONFIG_HOME/kemet/ignore` (falling back to
+/// `~/.config/kemet/ignore` when `XDG_CONFIG_HOME` isn't set). Patterns in
+/// this file apply to every search, independent of any repo-local
+/// `.gitignore`/`.ignore`. Returns `None` if neither variable is set.
+fn global_ignore_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("kemet").join("ignore"))
+}
+
+/// Builds the `ignore_stack` a walk should start with: just the global
+/// ignore file's rules (anchored at `config.path`, the walk root), or empty
+/// when `--no-ignore` is set. `walk_dir` pushes each directory's own
+/// `.gitignore`/`.ignore` on top of this as it descends.
+fn initial_ignore_stack(config: &Config) -> Vec<(PathBuf, IgnoreMatcher)> {
+    if config.no_ignore {
+        Vec::new()
+    } else {
+        vec![(config.path.clone(), IgnoreMatcher::load_global())]
+    }
+}
+
+/// All the ignore rules contributed by a single directory's `.gitignore`
+/// and `.ignore` files.
+struct IgnoreMatcher {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreMatcher {
+    /// Loads the ignore rules defined directly inside `dir`, if any.
+    fn load(dir: &Path) -> Self {
+        let mut rules = Vec::new();
+        for file_name in [".gitignore", ".ignore"] {
+            if let Ok(content) = fs::read_to_string(dir.join(file_name)) {
+                rules.extend(content.lines().filter_map(IgnoreRule::parse));
+            }
+        }
+        Self { rules }
+    }
+
+    /// Loads the user's global ignore file (see `global_ignore_path`), if
+    /// one exists.
+    fn load_global() -> Self {
+        let rules = global_ignore_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().filter_map(IgnoreRule::parse).collect())
+            .unwrap_or_default();
+        Self { rules }
+    }
+
+    /// Returns this matcher's verdict for `rel_path` (relative to the
+    /// directory this matcher was loaded from), or `None` if none of its
+    /// rules mention the path. The last matching rule wins, so a later
+    /// negation re-includes a path excluded earlier in the same file.
+    fn matches(&self, rel_path: &str, is_dir: bool) -> Option<bool> {
+        let mut verdict = None;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            if rule.regex.is_match(rel_path) {
+                verdict = Some(!rule.negate);
+            }
+        }
+        verdict
+    }
 }
 
 /// Main search engine
 struct SearchEngine<'a> {
     config: &'a Config,
-    matches: Vec<Match>,
+    match_count: usize,
     files_searched: usize,
     errors: Vec<String>,
 }
@@ -256,63 +703,87 @@ impl<'a> SearchEngine<'a> {
     fn new(config: &'a Config) -> Self {
         Self {
             config,
-            matches: Vec::new(),
+            match_count: 0,
             files_searched: 0,
             errors: Vec::new(),
         }
     }
 
-    fn search(&mut self) -> io::Result<()> {
+    /// Runs the search, writing each match as soon as it is found rather
+    /// than buffering the whole tree in memory. `cancelled` is polled by
+    /// the walker and workers so a Ctrl-C can stop the walk promptly while
+    /// still leaving room to print a partial summary.
+    fn search(&mut self, cancelled: &AtomicBool) -> io::Result<()> {
         // Create output writer
         let mut writer = OutputWriter::new(self.config.output_file.as_ref())?;
 
-        writer.writeln(&format!(
-            "Searching for \"{}\" in {} and all subfolders...",
-            self.config.search_text,
-            self.config.path.display()
-        ))?;
-
-        if self.config.case_sensitive {
-            writer.writeln("Case-sensitive search enabled")?;
-        }
+        if self.config.format == Format::Text {
+            if self.config.use_stdin {
+                writer.writeln(&format!(
+                    "Searching for \"{}\" on stdin...",
+                    self.config.search_text
+                ))?;
+            } else {
+                writer.writeln(&format!(
+                    "Searching for \"{}\" in {} and all subfolders...",
+                    self.config.search_text,
+                    self.config.path.display()
+                ))?;
+            }
 
-        writer.writeln(&format!(
-            "Extensions: {}",
-            self.config.extensions.join(", ")
-        ))?;
-        writer.write_empty_line()?;
+            if self.config.case_sensitive {
+                writer.writeln("Case-sensitive search enabled")?;
+            }
 
-        self.visit_dir(&self.config.path)?;
+            if !self.config.use_stdin {
+                writer.writeln(&format!(
+                    "Extensions: {}",
+                    self.config.extensions.join(", ")
+                ))?;
+            }
+            writer.write_empty_line()?;
+        }
 
-        // Display results
-        if self.matches.is_empty() {
-            writer.writeln("No matches found.")?;
+        if self.config.use_stdin {
+            self.run_stdin(&mut writer, cancelled)?;
         } else {
-            writer.writeln(&format!(
-                "Found {} matches in {} files:",
-                self.matches.len(),
-                self.files_searched
-            ))?;
-            writer.write_empty_line()?;
+            self.run_workers(&mut writer, cancelled)?;
+        }
 
-            for match_result in &self.matches {
-                writer.writeln(&match_result.format_output(self.config))?;
-            }
+        if self.config.format == Format::Text && self.match_count == 0 {
+            writer.writeln("No matches found.")?;
         }
 
-        // Display summary
         writer.write_empty_line()?;
-        writer.writeln(&format!(
-            "Summary: {} files searched, {} matches found",
-            self.files_searched,
-            self.matches.len()
-        ))?;
 
-        if !self.errors.is_empty() {
-            writer.write_empty_line()?;
-            writer.writeln("Errors encountered:")?;
-            for error in &self.errors {
-                writer.writeln(&format!("  {}", error))?;
+        let cancelled_note = if cancelled.load(Ordering::Relaxed) {
+            " (cancelled)"
+        } else {
+            ""
+        };
+
+        match self.config.format {
+            Format::Text => {
+                writer.writeln(&format!(
+                    "Summary: {} files searched, {} matches found{}",
+                    self.files_searched, self.match_count, cancelled_note
+                ))?;
+
+                if !self.errors.is_empty() {
+                    writer.write_empty_line()?;
+                    writer.writeln("Errors encountered:")?;
+                    for error in &self.errors {
+                        writer.writeln(&format!("  {}", error))?;
+                    }
+                }
+            }
+            Format::Json => {
+                writer.writeln(&format!(
+                    r#"{{"type":"summary","files_searched":{},"matches":{},"cancelled":{}}}"#,
+                    self.files_searched,
+                    self.match_count,
+                    cancelled.load(Ordering::Relaxed)
+                ))?;
             }
         }
 
@@ -324,51 +795,179 @@ impl<'a> SearchEngine<'a> {
         Ok(())
     }
 
-    fn visit_dir(&mut self, dir: &Path) -> io::Result<()> {
-        let entries = match fs::read_dir(dir) {
-            Ok(entries) => entries,
+    /// Writes a single match (and any context lines it carries) to `writer`
+    /// immediately, tracking just enough state across calls to emit JSON
+    /// `begin` events and text-mode `--` group separators correctly.
+    fn write_match(
+        &self,
+        match_result: &Match,
+        writer: &mut OutputWriter,
+        last_printed: &mut Option<(PathBuf, usize)>,
+        current_json_file: &mut Option<PathBuf>,
+    ) -> io::Result<()> {
+        match self.config.format {
+            Format::Json => {
+                if current_json_file.as_ref() != Some(&match_result.file_path) {
+                    writer.writeln(&format!(
+                        r#"{{"type":"begin","path":"{}"}}"#,
+                        json_escape(&match_result.file_path.display().to_string())
+                    ))?;
+                    *current_json_file = Some(match_result.file_path.clone());
+                }
+                writer.writeln(&match_result.format_output(self.config))
+            }
+            Format::Text => {
+                let mut lines: Vec<(usize, &str, bool)> = Vec::new();
+                for (line_no, content) in &match_result.before_context {
+                    lines.push((*line_no, content.as_str(), false));
+                }
+                lines.push((
+                    match_result.line_number,
+                    match_result.line_content.as_deref().unwrap_or(""),
+                    true,
+                ));
+                for (line_no, content) in &match_result.after_context {
+                    lines.push((*line_no, content.as_str(), false));
+                }
+
+                for (line_no, content, is_match_line) in lines {
+                    // Overlapping context/match windows can see the same
+                    // line twice; skip anything already printed.
+                    if let Some((path, last_line)) = last_printed.as_ref() {
+                        if *path == match_result.file_path && line_no <= *last_line {
+                            continue;
+                        }
+                        if *path == match_result.file_path && line_no > *last_line + 1 {
+                            writer.writeln("--")?;
+                        }
+                    }
+
+                    if is_match_line {
+                        writer.writeln(&match_result.format_output(self.config))?;
+                    } else {
+                        writer.writeln(&format!(
+                            "{} (Line {})- {}",
+                            match_result.file_path.display(),
+                            line_no,
+                            content.trim()
+                        ))?;
+                    }
+
+                    *last_printed = Some((match_result.file_path.clone(), line_no));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Walks the directory tree, enumerating matching files and dispatching
+    /// them to the worker pool via `tx`. Runs on its own walker thread.
+    ///
+    /// `ignore_stack` holds one matcher per ancestor directory (unless
+    /// `--no-ignore` is set); deeper matchers are consulted first so that a
+    /// nested `.gitignore` can override its parent. `cancelled` is checked
+    /// between entries so a Ctrl-C stops the walk promptly.
+    fn walk_dir(
+        config: &Config,
+        dir: &Path,
+        tx: &mpsc::SyncSender<(usize, PathBuf)>,
+        next_seq: &mut usize,
+        errors: &mut Vec<String>,
+        ignore_stack: &mut Vec<(PathBuf, IgnoreMatcher)>,
+        cancelled: &AtomicBool,
+    ) {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut entries: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries.collect(),
             Err(e) => {
-                self.errors
-                    .push(format!("Could not read directory {}: {}", dir.display(), e));
-                return Ok(());
+                errors.push(format!("Could not read directory {}: {}", dir.display(), e));
+                return;
             }
         };
+        // Sorting by file name keeps traversal order (and therefore the
+        // sequence numbers handed out below) deterministic across runs,
+        // regardless of what order the OS returns directory entries in.
+        entries.sort_by(|a, b| {
+            let name_a = a.as_ref().map(|e| e.file_name());
+            let name_b = b.as_ref().map(|e| e.file_name());
+            name_a.ok().cmp(&name_b.ok())
+        });
+
+        let pushed = !config.no_ignore;
+        if pushed {
+            ignore_stack.push((dir.to_path_buf(), IgnoreMatcher::load(dir)));
+        }
 
         for entry_result in entries {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
             let entry = match entry_result {
                 Ok(entry) => entry,
                 Err(e) => {
-                    self.errors
-                        .push(format!("Could not read entry in {}: {}", dir.display(), e));
+                    errors.push(format!("Could not read entry in {}: {}", dir.display(), e));
                     continue;
                 }
             };
 
+            if entry.file_name() == ".git" {
+                continue;
+            }
+
             let path = entry.path();
+            let is_dir = path.is_dir();
 
-            if path.is_dir() {
-                // Recursively search subdirectories
-                if let Err(e) = self.visit_dir(&path) {
-                    self.errors.push(format!(
-                        "Error searching directory {}: {}",
-                        path.display(),
-                        e
-                    ));
+            if !config.no_ignore && Self::is_ignored(&path, is_dir, ignore_stack) {
+                continue;
+            }
+
+            if is_dir {
+                // Recursively walk subdirectories
+                Self::walk_dir(config, &path, tx, next_seq, errors, ignore_stack, cancelled);
+            } else if path.is_file() && Self::should_search_file(config, &path) {
+                // Sequence numbers are handed out in sorted traversal order,
+                // so the consumer can restore that order regardless of
+                // which worker finishes a given file first.
+                let seq = *next_seq;
+                *next_seq += 1;
+                // If every worker has hung up the queue is no longer being
+                // drained, so stop producing more work.
+                if tx.send((seq, path)).is_err() {
+                    return;
                 }
-            } else if path.is_file() && self.should_search_file(&path) {
-                self.search_in_file(&path);
             }
         }
 
-        Ok(())
+        if pushed {
+            ignore_stack.pop();
+        }
+    }
+
+    /// Consults `ignore_stack` from the deepest directory outward, returning
+    /// the first matcher's verdict (i.e. the closest `.gitignore` wins).
+    fn is_ignored(path: &Path, is_dir: bool, ignore_stack: &[(PathBuf, IgnoreMatcher)]) -> bool {
+        for (ignore_dir, matcher) in ignore_stack.iter().rev() {
+            if let Ok(rel_path) = path.strip_prefix(ignore_dir) {
+                let rel_path = rel_path.to_string_lossy().replace('\\', "/");
+                if let Some(verdict) = matcher.matches(&rel_path, is_dir) {
+                    return verdict;
+                }
+            }
+        }
+        false
     }
 
-    fn should_search_file(&self, path: &Path) -> bool {
+    fn should_search_file(config: &Config, path: &Path) -> bool {
         path.extension()
             .and_then(OsStr::to_str)
             .map(|ext| {
                 let ext_with_dot = format!(".{}", ext);
-                self.config
+                config
                     .extensions
                     .iter()
                     .any(|e| e.eq_ignore_ascii_case(&ext_with_dot))
@@ -376,11 +975,21 @@ impl<'a> SearchEngine<'a> {
             .unwrap_or(false)
     }
 
-    fn search_in_file(&mut self, file_path: &Path) {
+    /// Scans a single file for matches, invoking `on_match` as each one is
+    /// found (see `scan_reader`). Runs on a worker thread; the caller is
+    /// responsible for attributing matches to this file's place in the
+    /// overall output order.
+    fn search_in_file(
+        config: &Config,
+        file_path: &Path,
+        errors: &mut Vec<String>,
+        cancelled: &AtomicBool,
+        on_match: &mut dyn FnMut(Match),
+    ) {
         let file = match fs::File::open(file_path) {
             Ok(file) => file,
             Err(e) => {
-                self.errors.push(format!(
+                errors.push(format!(
                     "Could not open file {}: {}",
                     file_path.display(),
                     e
@@ -389,49 +998,717 @@ impl<'a> SearchEngine<'a> {
             }
         };
 
-        self.files_searched += 1;
-        let reader = BufReader::new(file);
+        Self::scan_reader(
+            config,
+            file_path,
+            BufReader::new(file),
+            errors,
+            cancelled,
+            on_match,
+        )
+    }
 
-        let search_text = if self.config.case_sensitive {
-            self.config.search_text.clone()
+    /// Scans lines from any `BufRead` source for matches, calling
+    /// `on_match` as soon as each one's `after_context` requirement (if any)
+    /// is satisfied, rather than collecting the whole source into a `Vec`
+    /// first — so a multi-gigabyte file starts producing output well before
+    /// it's been fully read, instead of only once EOF is reached. Shared by
+    /// file scanning and `--stdin` so both paths apply exactly the same
+    /// matching and context-window logic. `source_path` is recorded on each
+    /// `Match` (e.g. the file path, or `<stdin>`) and used only for display
+    /// and error messages.
+    fn scan_reader<R: BufRead>(
+        config: &Config,
+        source_path: &Path,
+        reader: R,
+        errors: &mut Vec<String>,
+        cancelled: &AtomicBool,
+        on_match: &mut dyn FnMut(Match),
+    ) {
+        let search_text = if config.case_sensitive {
+            config.search_text.clone()
         } else {
-            self.config.search_text.to_lowercase()
+            config.search_text.to_lowercase()
         };
 
+        // JSON output always needs the line text and submatches; plain text
+        // output only needs them when `-l/--show-lines` is set (which
+        // Config::new also forces on whenever context lines are requested).
+        let need_content = config.show_line_content || config.format == Format::Json;
+
+        // Ring buffer of the last `before_context` lines seen, for lines
+        // that turn out to precede a match.
+        let mut before_buf: std::collections::VecDeque<(usize, String)> =
+            std::collections::VecDeque::with_capacity(config.before_context);
+        // Matches still collecting their `after_context`, keyed by a
+        // monotonically increasing id (rather than a `Vec` index) so that
+        // flushing one out via `on_match` doesn't shift the others.
+        let mut open_matches: std::collections::BTreeMap<usize, Match> =
+            std::collections::BTreeMap::new();
+        let mut pending_after: Vec<(usize, usize)> = Vec::new();
+        let mut next_match_id = 0usize;
+
         for (line_number, line_result) in reader.lines().enumerate() {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
             match line_result {
                 Ok(line) => {
-                    let line_to_check = if self.config.case_sensitive {
-                        line.clone()
+                    let line_no = line_number + 1;
+
+                    if !pending_after.is_empty() {
+                        let mut finished_ids = Vec::new();
+                        for (match_id, remaining) in pending_after.iter_mut() {
+                            if let Some(m) = open_matches.get_mut(match_id) {
+                                m.after_context.push((line_no, line.clone()));
+                            }
+                            *remaining -= 1;
+                            if *remaining == 0 {
+                                finished_ids.push(*match_id);
+                            }
+                        }
+                        pending_after.retain(|(_, remaining)| *remaining > 0);
+                        for match_id in finished_ids {
+                            if let Some(m) = open_matches.remove(&match_id) {
+                                on_match(m);
+                            }
+                        }
+                    }
+
+                    let (is_match, matched_ranges) = if let Some(re) = &config.regex {
+                        let ranges: Vec<(usize, usize)> = if need_content {
+                            re.find_iter(&line).map(|m| (m.start(), m.end())).collect()
+                        } else {
+                            Vec::new()
+                        };
+                        (re.is_match(&line), ranges)
+                    } else if config.case_sensitive {
+                        let ranges: Vec<(usize, usize)> = if need_content {
+                            line.match_indices(&search_text)
+                                .map(|(start, matched)| (start, start + matched.len()))
+                                .collect()
+                        } else {
+                            Vec::new()
+                        };
+                        (line.contains(&search_text), ranges)
                     } else {
-                        line.to_lowercase()
+                        // Located against the original `line`, not a
+                        // separately lowercased copy, so offsets stay valid
+                        // even when case folding changes a character's byte
+                        // length (see `find_case_insensitive`).
+                        if need_content {
+                            let ranges = find_case_insensitive(&line, &search_text);
+                            (!ranges.is_empty(), ranges)
+                        } else {
+                            (contains_case_insensitive(&line, &search_text), Vec::new())
+                        }
                     };
 
-                    if line_to_check.contains(&search_text) {
-                        let line_content = if self.config.show_line_content {
-                            Some(line)
+                    if is_match {
+                        let line_content = if need_content {
+                            Some(line.clone())
                         } else {
                             None
                         };
+                        let before_context = before_buf.iter().cloned().collect();
 
-                        self.matches.push(Match::new(
-                            file_path.to_path_buf(),
-                            line_number + 1,
+                        let new_match = Match::new(
+                            source_path.to_path_buf(),
+                            line_no,
                             line_content,
-                        ));
+                            matched_ranges,
+                            before_context,
+                            Vec::new(),
+                        );
+
+                        if config.after_context > 0 {
+                            let match_id = next_match_id;
+                            next_match_id += 1;
+                            open_matches.insert(match_id, new_match);
+                            pending_after.push((match_id, config.after_context));
+                        } else {
+                            on_match(new_match);
+                        }
+                    }
+
+                    if config.before_context > 0 {
+                        if before_buf.len() == config.before_context {
+                            before_buf.pop_front();
+                        }
+                        before_buf.push_back((line_no, line));
                     }
                 }
                 Err(e) => {
-                    self.errors.push(format!(
-                        "Could not read line {} in file {}: {}",
+                    errors.push(format!(
+                        "Could not read line {} in {}: {}",
                         line_number + 1,
-                        file_path.display(),
+                        source_path.display(),
                         e
                     ));
                     break;
                 }
             }
         }
+
+        // Whatever's left never got its full `after_context` (EOF or
+        // cancellation cut the source short); flush it anyway, in the order
+        // each match was found.
+        for (_, m) in open_matches {
+            on_match(m);
+        }
+    }
+
+    /// Writes `match_result`, counting it towards `self.match_count`, and
+    /// stops the search by setting `cancelled` if the write itself fails.
+    /// Shared by the live-arrival and buffered-flush paths in
+    /// `run_workers`'s receive loop, and by `run_stdin_reader`.
+    fn emit_match(
+        &mut self,
+        match_result: &Match,
+        writer: &mut OutputWriter,
+        last_printed: &mut Option<(PathBuf, usize)>,
+        current_json_file: &mut Option<PathBuf>,
+        cancelled: &AtomicBool,
+    ) -> io::Result<()> {
+        self.match_count += 1;
+        self.write_match(match_result, writer, last_printed, current_json_file)
+            .inspect_err(|_| cancelled.store(true, Ordering::SeqCst))
+    }
+
+    /// Runs the walker thread and the worker pool, writing matches to
+    /// `writer` as soon as their file's turn comes up in the sorted
+    /// traversal order handed out by `walk_dir`, rather than waiting for
+    /// every file to finish first. A worker streams each of its file's
+    /// matches over as soon as `scan_reader` produces them (rather than
+    /// waiting for the whole file), followed by a `None` once that file is
+    /// done; matches for files that aren't at the front of the order yet
+    /// are held in a small reorder buffer (keyed by sequence number) until
+    /// their file's predecessors have all finished, which keeps output
+    /// order deterministic ((file_path, line_number), via sorted traversal)
+    /// regardless of which worker thread finishes a given file first, or
+    /// how its matches interleave with another file's while both are being
+    /// scanned concurrently. `cancelled` is threaded through to the walker
+    /// and workers so a Ctrl-C (or a write failure) stops the search
+    /// promptly.
+    fn run_workers(&mut self, writer: &mut OutputWriter, cancelled: &AtomicBool) -> io::Result<()> {
+        let config = self.config;
+        let (path_tx, path_rx) = mpsc::sync_channel::<(usize, PathBuf)>(256);
+        let path_rx = Mutex::new(path_rx);
+        let (result_tx, result_rx) = mpsc::channel::<(usize, Option<Match>)>();
+
+        let (files_searched, errors, write_err) = std::thread::scope(|scope| {
+            let walker = scope.spawn(move || {
+                let mut walk_errors = Vec::new();
+                let mut ignore_stack = initial_ignore_stack(config);
+                let mut next_seq = 0usize;
+                Self::walk_dir(
+                    config,
+                    &config.path,
+                    &path_tx,
+                    &mut next_seq,
+                    &mut walk_errors,
+                    &mut ignore_stack,
+                    cancelled,
+                );
+                walk_errors
+            });
+
+            let workers: Vec<_> = (0..config.threads)
+                .map(|_| {
+                    let result_tx = result_tx.clone();
+                    let path_rx = &path_rx;
+                    scope.spawn(move || {
+                        let mut local_files_searched = 0usize;
+                        let mut local_errors = Vec::new();
+
+                        loop {
+                            let next_path = path_rx.lock().unwrap().recv();
+                            match next_path {
+                                Ok((seq, path)) => {
+                                    local_files_searched += 1;
+                                    Self::search_in_file(
+                                        config,
+                                        &path,
+                                        &mut local_errors,
+                                        cancelled,
+                                        &mut |m| {
+                                            let _ = result_tx.send((seq, Some(m)));
+                                        },
+                                    );
+                                    if result_tx.send((seq, None)).is_err() {
+                                        break;
+                                    }
+                                }
+                                Err(_) => break,
+                            }
+                        }
+
+                        (local_files_searched, local_errors)
+                    })
+                })
+                .collect();
+
+            // Drop the original sender so the channel closes once every
+            // worker's clone has also been dropped.
+            drop(result_tx);
+
+            let mut last_printed = None;
+            let mut current_json_file = None;
+            let mut write_err = None;
+            // Matches that arrived ahead of their file's turn, keyed by
+            // sequence number, waiting for `next_seq` to catch up to them.
+            let mut pending: std::collections::BTreeMap<usize, Vec<Match>> =
+                std::collections::BTreeMap::new();
+            // Sequence numbers whose file has finished (sent its `None`),
+            // but which may still be waiting behind an earlier, unfinished
+            // sequence number.
+            let mut finished: std::collections::HashSet<usize> = std::collections::HashSet::new();
+            let mut next_seq = 0usize;
+
+            'recv: while let Ok((seq, payload)) = result_rx.recv() {
+                match payload {
+                    Some(m) if seq == next_seq => {
+                        if let Err(e) = self.emit_match(
+                            &m,
+                            writer,
+                            &mut last_printed,
+                            &mut current_json_file,
+                            cancelled,
+                        ) {
+                            write_err = Some(e);
+                            break 'recv;
+                        }
+                    }
+                    Some(m) => pending.entry(seq).or_default().push(m),
+                    None => {
+                        finished.insert(seq);
+                    }
+                }
+
+                while finished.contains(&next_seq) {
+                    if let Some(buffered) = pending.remove(&next_seq) {
+                        for m in buffered {
+                            if let Err(e) = self.emit_match(
+                                &m,
+                                writer,
+                                &mut last_printed,
+                                &mut current_json_file,
+                                cancelled,
+                            ) {
+                                write_err = Some(e);
+                                break 'recv;
+                            }
+                        }
+                    }
+                    finished.remove(&next_seq);
+                    next_seq += 1;
+                }
+            }
+
+            let mut files_searched = 0;
+            let mut errors = walker.join().expect("walker thread panicked");
+
+            for worker in workers {
+                let (worker_files_searched, worker_errors) =
+                    worker.join().expect("worker thread panicked");
+                files_searched += worker_files_searched;
+                errors.extend(worker_errors);
+            }
+
+            (files_searched, errors, write_err)
+        });
+
+        self.files_searched = files_searched;
+        self.errors = errors;
+
+        match write_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Scans stdin for matches and writes them to `writer` once stdin is
+    /// exhausted. There's exactly one input stream to read, so (unlike
+    /// `run_workers`) there's no cross-file ordering to restore: stdin's
+    /// line order already is the output order.
+    fn run_stdin(&mut self, writer: &mut OutputWriter, cancelled: &AtomicBool) -> io::Result<()> {
+        let stdin = io::stdin();
+        self.run_stdin_reader(stdin.lock(), writer, cancelled)
+    }
+
+    /// Shared by `run_stdin` and tests: scans `reader` as if it were stdin.
+    fn run_stdin_reader<R: BufRead>(
+        &mut self,
+        reader: R,
+        writer: &mut OutputWriter,
+        cancelled: &AtomicBool,
+    ) -> io::Result<()> {
+        let config = self.config;
+        let mut errors = Vec::new();
+        let mut last_printed = None;
+        let mut current_json_file = None;
+        let mut write_err = None;
+
+        Self::scan_reader(
+            config,
+            Path::new("<stdin>"),
+            reader,
+            &mut errors,
+            cancelled,
+            &mut |m| {
+                if write_err.is_some() {
+                    return;
+                }
+                if let Err(e) = self.emit_match(
+                    &m,
+                    writer,
+                    &mut last_printed,
+                    &mut current_json_file,
+                    cancelled,
+                ) {
+                    write_err = Some(e);
+                }
+            },
+        );
+
+        self.files_searched = 1;
+        self.errors = errors;
+
+        match write_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Result of running a replacement pass over a single file.
+struct FileReplaceOutcome {
+    file_path: PathBuf,
+    replacements: usize,
+    /// Populated only in `--dry-run` mode: (line_number, old_line, new_line).
+    diffs: Vec<(usize, String, String)>,
+}
+
+/// Rewrites matching files in place, substituting each match with the
+/// configured `--replace` text. Shares `SearchEngine`'s walker/worker-pool
+/// architecture and ignore-file handling, but each worker rewrites whole
+/// files instead of reporting individual line matches.
+struct ReplaceEngine<'a> {
+    config: &'a Config,
+    files_searched: usize,
+    files_changed: usize,
+    replacements: usize,
+    errors: Vec<String>,
+}
+
+impl<'a> ReplaceEngine<'a> {
+    fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            files_searched: 0,
+            files_changed: 0,
+            replacements: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Runs the replace pass, writing a progress line per changed file as
+    /// soon as it's processed, then a final summary.
+    fn run(&mut self, cancelled: &AtomicBool) -> io::Result<()> {
+        let mut writer = OutputWriter::new(self.config.output_file.as_ref())?;
+        let replace_text = self.config.replace.as_deref().unwrap_or_default();
+
+        writer.writeln(&format!(
+            "{} \"{}\" with \"{}\" in {} and all subfolders...",
+            if self.config.dry_run {
+                "Previewing replacement of"
+            } else {
+                "Replacing"
+            },
+            self.config.search_text,
+            replace_text,
+            self.config.path.display()
+        ))?;
+        writer.write_empty_line()?;
+
+        self.run_workers(&mut writer, cancelled)?;
+
+        writer.write_empty_line()?;
+        let cancelled_note = if cancelled.load(Ordering::Relaxed) {
+            " (cancelled)"
+        } else {
+            ""
+        };
+        writer.writeln(&format!(
+            "Summary: {} files searched, {} files {}, {} replacements{}",
+            self.files_searched,
+            self.files_changed,
+            if self.config.dry_run {
+                "would change"
+            } else {
+                "changed"
+            },
+            self.replacements,
+            cancelled_note
+        ))?;
+
+        if !self.errors.is_empty() {
+            writer.write_empty_line()?;
+            writer.writeln("Errors encountered:")?;
+            for error in &self.errors {
+                writer.writeln(&format!("  {}", error))?;
+            }
+        }
+
+        if let Some(output_path) = &self.config.output_file {
+            println!("Results have been written to: {}", output_path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Walks the directory tree and dispatches files to a worker pool,
+    /// mirroring `SearchEngine::run_workers`.
+    fn run_workers(&mut self, writer: &mut OutputWriter, cancelled: &AtomicBool) -> io::Result<()> {
+        let config = self.config;
+        let (path_tx, path_rx) = mpsc::sync_channel::<(usize, PathBuf)>(256);
+        let path_rx = Mutex::new(path_rx);
+        let (outcome_tx, outcome_rx) = mpsc::channel::<FileReplaceOutcome>();
+
+        let (files_searched, errors, write_err) = std::thread::scope(|scope| {
+            let walker = scope.spawn(move || {
+                let mut walk_errors = Vec::new();
+                let mut ignore_stack = initial_ignore_stack(config);
+                let mut next_seq = 0usize;
+                SearchEngine::walk_dir(
+                    config,
+                    &config.path,
+                    &path_tx,
+                    &mut next_seq,
+                    &mut walk_errors,
+                    &mut ignore_stack,
+                    cancelled,
+                );
+                walk_errors
+            });
+
+            let workers: Vec<_> = (0..config.threads)
+                .map(|_| {
+                    let outcome_tx = outcome_tx.clone();
+                    let path_rx = &path_rx;
+                    scope.spawn(move || {
+                        let mut local_files_searched = 0usize;
+                        let mut local_errors = Vec::new();
+
+                        loop {
+                            let next_path = path_rx.lock().unwrap().recv();
+                            match next_path {
+                                Ok((_seq, path)) => {
+                                    local_files_searched += 1;
+                                    Self::replace_in_file(
+                                        config,
+                                        &path,
+                                        &outcome_tx,
+                                        &mut local_errors,
+                                        cancelled,
+                                    );
+                                }
+                                Err(_) => break,
+                            }
+                        }
+
+                        (local_files_searched, local_errors)
+                    })
+                })
+                .collect();
+
+            // Drop the original sender so the channel closes once every
+            // worker's clone has also been dropped.
+            drop(outcome_tx);
+
+            let mut write_err = None;
+
+            while let Ok(outcome) = outcome_rx.recv() {
+                if outcome.replacements > 0 {
+                    self.files_changed += 1;
+                    self.replacements += outcome.replacements;
+                    if let Err(e) = self.write_outcome(&outcome, writer) {
+                        write_err = Some(e);
+                        cancelled.store(true, Ordering::SeqCst);
+                    }
+                }
+            }
+
+            let mut files_searched = 0;
+            let mut errors = walker.join().expect("walker thread panicked");
+
+            for worker in workers {
+                let (worker_files_searched, worker_errors) =
+                    worker.join().expect("worker thread panicked");
+                files_searched += worker_files_searched;
+                errors.extend(worker_errors);
+            }
+
+            (files_searched, errors, write_err)
+        });
+
+        self.files_searched = files_searched;
+        self.errors = errors;
+
+        match write_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn write_outcome(
+        &self,
+        outcome: &FileReplaceOutcome,
+        writer: &mut OutputWriter,
+    ) -> io::Result<()> {
+        writer.writeln(&format!(
+            "{} ({} replacement{})",
+            outcome.file_path.display(),
+            outcome.replacements,
+            if outcome.replacements == 1 { "" } else { "s" }
+        ))?;
+
+        for (line_no, old_line, new_line) in &outcome.diffs {
+            writer.writeln(&format!("  (Line {})- {}", line_no, old_line.trim()))?;
+            writer.writeln(&format!("  (Line {})+ {}", line_no, new_line.trim()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads `file_path`, substitutes every match on every line, and (unless
+    /// `--dry-run` is set) atomically replaces the original: the rewritten
+    /// content is written to a temp file in the same directory and only
+    /// `fs::rename`d over the original once the whole file has been
+    /// processed without error, so a mid-file failure never truncates the
+    /// source.
+    fn replace_in_file(
+        config: &Config,
+        file_path: &Path,
+        outcome_tx: &mpsc::Sender<FileReplaceOutcome>,
+        errors: &mut Vec<String>,
+        cancelled: &AtomicBool,
+    ) {
+        let content = match fs::read_to_string(file_path) {
+            Ok(content) => content,
+            Err(e) => {
+                errors.push(format!(
+                    "Could not read file {}: {}",
+                    file_path.display(),
+                    e
+                ));
+                return;
+            }
+        };
+
+        let replace_text = config.replace.as_deref().unwrap_or_default();
+        let mut new_lines = Vec::with_capacity(content.lines().count());
+        let mut diffs = Vec::new();
+        let mut replacements = 0usize;
+
+        for (line_number, line) in content.lines().enumerate() {
+            if cancelled.load(Ordering::Relaxed) {
+                errors.push(format!(
+                    "Replacement in {} cancelled before completion; file left unchanged",
+                    file_path.display()
+                ));
+                return;
+            }
+
+            let (new_line, count) = Self::replace_line(config, line, replace_text);
+            if count > 0 {
+                replacements += count;
+                if config.dry_run {
+                    diffs.push((line_number + 1, line.to_string(), new_line.clone()));
+                }
+            }
+            new_lines.push(new_line);
+        }
+
+        if replacements == 0 {
+            return;
+        }
+
+        if !config.dry_run {
+            let mut new_content = new_lines.join("\n");
+            if content.ends_with('\n') {
+                new_content.push('\n');
+            }
+
+            let dir = file_path.parent().unwrap_or_else(|| Path::new("."));
+            let temp_name = format!(
+                ".{}.kemet-tmp",
+                file_path
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .unwrap_or("replace")
+            );
+            let temp_path = dir.join(temp_name);
+
+            if let Err(e) = fs::write(&temp_path, &new_content) {
+                errors.push(format!(
+                    "Could not write temp file for {}: {}",
+                    file_path.display(),
+                    e
+                ));
+                return;
+            }
+
+            if let Err(e) = fs::rename(&temp_path, file_path) {
+                errors.push(format!("Could not replace {}: {}", file_path.display(), e));
+                let _ = fs::remove_file(&temp_path);
+                return;
+            }
+        }
+
+        let _ = outcome_tx.send(FileReplaceOutcome {
+            file_path: file_path.to_path_buf(),
+            replacements,
+            diffs,
+        });
+    }
+
+    /// Substitutes every match on a single line, returning the rewritten
+    /// line and how many replacements were made. In regex mode this uses
+    /// `Regex::replace_all`, so `replace_text` may reference capture
+    /// groups (`$1`, `$2`, ...); in literal mode the match is located the
+    /// same way `SearchEngine::scan_reader` does and spliced out directly.
+    fn replace_line(config: &Config, line: &str, replace_text: &str) -> (String, usize) {
+        if let Some(re) = &config.regex {
+            let count = re.find_iter(line).count();
+            if count == 0 {
+                return (line.to_string(), 0);
+            }
+            (re.replace_all(line, replace_text).into_owned(), count)
+        } else {
+            let search_text = if config.case_sensitive {
+                config.search_text.clone()
+            } else {
+                config.search_text.to_lowercase()
+            };
+            // Located against the original `line`, not a separately
+            // lowercased copy, so offsets stay valid even when case folding
+            // changes a character's byte length (see `find_case_insensitive`).
+            let ranges: Vec<(usize, usize)> = if config.case_sensitive {
+                line.match_indices(&search_text)
+                    .map(|(start, matched)| (start, start + matched.len()))
+                    .collect()
+            } else {
+                find_case_insensitive(line, &search_text)
+            };
+
+            let mut new_line = line.to_string();
+            for (start, end) in ranges.iter().rev() {
+                new_line.replace_range(*start..*end, replace_text);
+            }
+            (new_line, ranges.len())
+        }
     }
 }
 
@@ -444,10 +1721,320 @@ fn main() {
         }
     };
 
-    let mut search_engine = SearchEngine::new(&config);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&cancelled);
+    if let Err(e) = ctrlc::set_handler(move || {
+        handler_flag.store(true, Ordering::SeqCst);
+    }) {
+        eprintln!("Warning: could not install Ctrl-C handler: {}", e);
+    }
+
+    if config.replace.is_some() {
+        let mut replace_engine = ReplaceEngine::new(&config);
+        if let Err(e) = replace_engine.run(&cancelled) {
+            eprintln!("Replace failed: {}", e);
+            std::process::exit(1);
+        }
+    } else {
+        let mut search_engine = SearchEngine::new(&config);
+        if let Err(e) = search_engine.search(&cancelled) {
+            eprintln!("Search failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(search_text: &str, regex: Option<Regex>, case_sensitive: bool) -> Config {
+        Config {
+            path: PathBuf::from("."),
+            search_text: search_text.to_string(),
+            extensions: Vec::new(),
+            case_sensitive,
+            show_line_content: false,
+            output_file: None,
+            regex,
+            threads: 1,
+            no_ignore: false,
+            format: Format::Text,
+            before_context: 0,
+            after_context: 0,
+            use_stdin: false,
+            replace: None,
+            dry_run: false,
+        }
+    }
+
+    // Collision-free per-test scratch directory: worker threads in
+    // `run_workers` touch the filesystem, so tests that exercise it need a
+    // directory no other test (or concurrent test run) will also pick.
+    fn unique_temp_dir(label: &str) -> PathBuf {
+        use std::sync::atomic::AtomicUsize;
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir =
+            std::env::temp_dir().join(format!("kemet-test-{}-{}-{}", std::process::id(), label, n));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn run_workers_stops_promptly_when_already_cancelled() {
+        let dir = unique_temp_dir("cancelled");
+        fs::write(dir.join("a.txt"), "needle\n").unwrap();
+
+        let mut config = test_config("needle", None, false);
+        config.path = dir;
+        config.no_ignore = true;
+        let cancelled = AtomicBool::new(true);
+        let mut engine = SearchEngine::new(&config);
+        let mut writer = OutputWriter::new(None).unwrap();
+
+        engine.run_workers(&mut writer, &cancelled).unwrap();
+
+        assert_eq!(engine.match_count, 0);
+        assert_eq!(engine.files_searched, 0);
+    }
+
+    #[test]
+    fn run_workers_orders_output_by_sorted_file_name_regardless_of_thread_count() {
+        let dir = unique_temp_dir("order");
+        for name in ["e.txt", "a.txt", "c.txt", "b.txt", "d.txt"] {
+            fs::write(dir.join(name), "needle\n").unwrap();
+        }
+        let out_path = dir.join("out.json");
+
+        let mut config = test_config("needle", None, false);
+        config.path = dir;
+        config.no_ignore = true;
+        config.extensions = vec![".txt".to_string()];
+        config.threads = 4;
+        config.format = Format::Json;
+        config.output_file = Some(out_path.clone());
+        let cancelled = AtomicBool::new(false);
+        let mut engine = SearchEngine::new(&config);
+        let mut writer = OutputWriter::new(config.output_file.as_ref()).unwrap();
+
+        engine.run_workers(&mut writer, &cancelled).unwrap();
+        drop(writer);
+
+        let output = fs::read_to_string(&out_path).unwrap();
+        let order: Vec<String> = output
+            .lines()
+            .filter(|line| line.contains(r#""type":"match""#))
+            .filter_map(|line| {
+                line.split(r#""path":""#)
+                    .nth(1)
+                    .and_then(|rest| rest.split('"').next())
+                    .map(|s| s.to_string())
+            })
+            .collect();
+
+        assert_eq!(order.len(), 5);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(order, sorted);
+    }
+
+    #[test]
+    fn run_stdin_reader_finds_matches_in_piped_input() {
+        let config = test_config("needle", None, false);
+        let cancelled = AtomicBool::new(false);
+        let mut engine = SearchEngine::new(&config);
+        let mut writer = OutputWriter::new(None).unwrap();
+
+        let input = std::io::Cursor::new(b"one\nneedle here\nthree\n".to_vec());
+        engine
+            .run_stdin_reader(input, &mut writer, &cancelled)
+            .unwrap();
+
+        assert_eq!(engine.match_count, 1);
+        assert_eq!(engine.files_searched, 1);
+    }
+
+    #[test]
+    fn scan_reader_groups_before_and_after_context_per_match() {
+        let mut config = test_config("needle", None, false);
+        config.before_context = 1;
+        config.after_context = 1;
+        config.show_line_content = true;
+        let cancelled = AtomicBool::new(false);
+        let mut errors = Vec::new();
+
+        let input = "line1\nneedle one\nline3\nline4\nneedle two\nline6\n";
+        let mut matches = Vec::new();
+        SearchEngine::scan_reader(
+            &config,
+            Path::new("t.txt"),
+            input.as_bytes(),
+            &mut errors,
+            &cancelled,
+            &mut |m| matches.push(m),
+        );
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].before_context, vec![(1, "line1".to_string())]);
+        assert_eq!(matches[0].after_context, vec![(3, "line3".to_string())]);
+        assert_eq!(matches[1].before_context, vec![(4, "line4".to_string())]);
+        assert_eq!(matches[1].after_context, vec![(6, "line6".to_string())]);
+    }
+
+    #[test]
+    fn ignore_rule_double_star_slash_matches_whole_path_components() {
+        // Regression test: `**/foo.rs` must match `foo.rs` and `sub/foo.rs`,
+        // but not `xfoo.rs` or `sub/xfoo.rs` (glob_to_regex_body previously
+        // translated `**/` to a bare `.*`, which matched any path merely
+        // ending in "foo.rs").
+        let rule = IgnoreRule::parse("**/foo.rs").unwrap();
+        assert!(rule.regex.is_match("foo.rs"));
+        assert!(rule.regex.is_match("sub/foo.rs"));
+        assert!(!rule.regex.is_match("xfoo.rs"));
+        assert!(!rule.regex.is_match("sub/xfoo.rs"));
+    }
+
+    #[test]
+    fn ignore_rule_plain_star_does_not_cross_path_separators() {
+        // Unanchored (no `/` in the pattern), so it matches the basename at
+        // any depth, but `*` within a single component must not match `/`.
+        let rule = IgnoreRule::parse("*.log").unwrap();
+        assert!(rule.regex.is_match("a.log"));
+        assert!(rule.regex.is_match("sub/a.log"));
+        assert!(!rule.regex.is_match("a.logx"));
+    }
+
+    #[test]
+    fn ignore_rule_trailing_double_star_matches_everything_under_dir() {
+        let rule = IgnoreRule::parse("build/**").unwrap();
+        assert!(rule.regex.is_match("build/a"));
+        assert!(rule.regex.is_match("build/a/b"));
+        assert!(!rule.regex.is_match("other/a"));
+    }
+
+    #[test]
+    fn ignore_rule_parse_skips_blank_lines_and_comments() {
+        assert!(IgnoreRule::parse("").is_none());
+        assert!(IgnoreRule::parse("   ").is_none());
+        assert!(IgnoreRule::parse("# a comment").is_none());
+    }
+
+    #[test]
+    fn initial_ignore_stack_empty_when_no_ignore_is_set() {
+        let mut config = test_config("needle", None, false);
+        config.no_ignore = true;
+        assert!(initial_ignore_stack(&config).is_empty());
+    }
+
+    #[test]
+    fn initial_ignore_stack_seeds_global_matcher_anchored_at_search_root() {
+        let mut config = test_config("needle", None, false);
+        config.path = PathBuf::from("/some/root");
+        let stack = initial_ignore_stack(&config);
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack[0].0, PathBuf::from("/some/root"));
+    }
+
+    #[test]
+    fn ignore_matcher_last_matching_rule_wins() {
+        let matcher = IgnoreMatcher {
+            rules: vec![
+                IgnoreRule::parse("*.log").unwrap(),
+                IgnoreRule::parse("!keep.log").unwrap(),
+            ],
+        };
+        assert_eq!(matcher.matches("a.log", false), Some(true));
+        assert_eq!(matcher.matches("keep.log", false), Some(false));
+        assert_eq!(matcher.matches("a.txt", false), None);
+    }
+
+    #[test]
+    fn ignore_matcher_dir_only_rule_skips_files() {
+        let matcher = IgnoreMatcher {
+            rules: vec![IgnoreRule::parse("target/").unwrap()],
+        };
+        assert_eq!(matcher.matches("target", true), Some(true));
+        assert_eq!(matcher.matches("target", false), None);
+    }
+
+    #[test]
+    fn scan_reader_literal_handles_length_changing_case_folding() {
+        // Same regression as replace_line_literal_handles_length_changing_case_folding,
+        // but for the search path: matched_ranges must index into the
+        // original line, not a separately lowercased copy one byte shorter.
+        let mut config = test_config("foo", None, false);
+        config.show_line_content = true;
+        let cancelled = AtomicBool::new(false);
+        let mut errors = Vec::new();
+        let mut matches = Vec::new();
+        SearchEngine::scan_reader(
+            &config,
+            Path::new("t.txt"),
+            "xİfoo\n".as_bytes(),
+            &mut errors,
+            &cancelled,
+            &mut |m| matches.push(m),
+        );
+
+        assert_eq!(matches.len(), 1);
+        let line = matches[0].line_content.as_deref().unwrap();
+        let (start, end) = matches[0].matched_ranges[0];
+        assert_eq!(&line[start..end], "foo");
+    }
+
+    #[test]
+    fn json_escape_handles_quotes_backslashes_and_control_chars() {
+        assert_eq!(json_escape("a\"b\\c"), "a\\\"b\\\\c");
+        assert_eq!(json_escape("line1\nline2\ttab"), "line1\\nline2\\ttab");
+        assert_eq!(json_escape("\u{1}"), "\\u0001");
+        assert_eq!(json_escape("plain"), "plain");
+    }
+
+    #[test]
+    fn replace_line_literal_replaces_every_occurrence() {
+        let config = test_config("foo", None, true);
+        let (result, count) = ReplaceEngine::replace_line(&config, "foo bar foo", "baz");
+        assert_eq!(result, "baz bar baz");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn replace_line_literal_is_case_insensitive_by_default() {
+        let config = test_config("foo", None, false);
+        let (result, count) = ReplaceEngine::replace_line(&config, "Foo bar", "baz");
+        assert_eq!(result, "baz bar");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn replace_line_literal_handles_length_changing_case_folding() {
+        // `İ` (U+0130) lowercases to `i̇`, two characters, one byte longer
+        // than the original. Matches must still be located in the
+        // original (unfolded) line, not a separately lowercased copy whose
+        // byte offsets would no longer line up.
+        let config = test_config("foo", None, false);
+        let (result, count) = ReplaceEngine::replace_line(&config, "xİfoo", "bar");
+        assert_eq!(result, "xİbar");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn replace_line_regex_expands_capture_groups() {
+        let regex = RegexBuilder::new(r"TODO\((\w+)\)").build().unwrap();
+        let config = test_config("TODO\\((\\w+)\\)", Some(regex), true);
+        let (result, count) =
+            ReplaceEngine::replace_line(&config, "TODO(alice): fix this", "FIXME($1)");
+        assert_eq!(result, "FIXME(alice): fix this");
+        assert_eq!(count, 1);
+    }
 
-    if let Err(e) = search_engine.search() {
-        eprintln!("Search failed: {}", e);
-        std::process::exit(1);
+    #[test]
+    fn replace_line_regex_no_match_leaves_line_untouched() {
+        let regex = RegexBuilder::new("TODO").build().unwrap();
+        let config = test_config("TODO", Some(regex), true);
+        let (result, count) = ReplaceEngine::replace_line(&config, "nothing here", "DONE");
+        assert_eq!(result, "nothing here");
+        assert_eq!(count, 0);
     }
 }